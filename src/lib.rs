@@ -1,61 +1,245 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
-    hash::Hash,
+    borrow::Borrow,
+    collections::{hash_map, HashMap},
+    hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
     marker::PhantomData,
 };
 
+mod typed;
+pub use typed::{Cons, Contains, Here, Nil, There, TypedMultiTypeMap};
+
 /// A map which supports keys with different types.
 /// Keys must be `'static` and implement [`Eq`] and [`Hash`].
 /// Values must be `'static`.
-pub struct MultiTypeMap<T> {
-    maps: HashMap<TypeId, Box<dyn Any>>,
+///
+/// The outer map, which dispatches on the key's [`TypeId`], is hashed with a [`BuildHasher`] `S`
+/// that defaults to [`TypeIdHasher`], since a [`TypeId`] is already a well-distributed 64-bit
+/// value and gains nothing from a full [`SipHash`](std::collections::hash_map::DefaultHasher) pass.
+pub struct MultiTypeMap<T, S = BuildHasherDefault<TypeIdHasher>> {
+    maps: HashMap<TypeId, ErasedMap<T>, S>,
     length: usize,
     _marker: PhantomData<T>,
 }
 
+/// A type-erased `HashMap<K, T>`, plus a pair of monomorphized functions (captured by
+/// [`ErasedMap::new`], the single constructor shared by every call site that lazily creates a
+/// per-type map) that know how to iterate over its values without knowing `K`. The concrete
+/// `HashMap<K, T>` type is otherwise lost once boxed.
+struct ErasedMap<T> {
+    map: Box<dyn Any>,
+    values: fn(&dyn Any) -> Box<dyn Iterator<Item = &T> + '_>,
+    values_mut: fn(&mut dyn Any) -> Box<dyn Iterator<Item = &mut T> + '_>,
+}
+
+impl<T: 'static> ErasedMap<T> {
+    /// Creates an empty erased map for the key type `K`.
+    fn new<K: 'static>() -> Self {
+        Self {
+            map: Box::<HashMap<K, T>>::default(),
+            values: downcast_values::<K, T>,
+            values_mut: downcast_values_mut::<K, T>,
+        }
+    }
+}
+
+fn downcast_values<K: 'static, T: 'static>(map: &dyn Any) -> Box<dyn Iterator<Item = &T> + '_> {
+    Box::new(
+        map.downcast_ref::<HashMap<K, T>>()
+            .expect("two different types should not have the same TypeId")
+            .values(),
+    )
+}
+
+fn downcast_values_mut<K: 'static, T: 'static>(
+    map: &mut dyn Any,
+) -> Box<dyn Iterator<Item = &mut T> + '_> {
+    Box::new(
+        map.downcast_mut::<HashMap<K, T>>()
+            .expect("two different types should not have the same TypeId")
+            .values_mut(),
+    )
+}
+
 impl<T: 'static> MultiTypeMap<T> {
     /// Creates an empty [`MultiTypeMap`].
     pub fn new() -> Self {
         Self {
-            maps: HashMap::new(),
+            maps: HashMap::default(),
+            length: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty [`MultiTypeMap`] whose outer, `TypeId`-keyed map has at least the
+    /// specified capacity. Inner per-type maps are still created lazily on first insert, so this
+    /// only pre-sizes the dispatch table, not any particular key type's storage.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            maps: HashMap::with_capacity_and_hasher(capacity, BuildHasherDefault::default()),
             length: 0,
             _marker: PhantomData,
         }
     }
+}
 
+impl<T: 'static, S: BuildHasher + Default> MultiTypeMap<T, S> {
     /// Inserts a value into the map. If the map did not have this key present, `None` is returned.
     /// If the map did have this key present, the value is updated, and the old value is returned.
     pub fn insert<K: 'static + Eq + Hash>(&mut self, key: K, value: T) -> Option<T> {
         self.length += 1;
-        self.map_mut().insert(key, value).map(|value| {
+        self.map_mut().insert(key, value).inspect(|_| {
             self.length -= 1;
-            value
         })
     }
 
     /// Removes a key from the map, returning the value at the key if the key was previously in the map.
-    pub fn remove<K: 'static + Eq + Hash>(&mut self, key: &K) -> Option<T> {
-        self.map_mut::<K>().remove(key).map(|value| {
+    ///
+    /// The key type `K` stored in the map must be specified explicitly (e.g. via turbofish), since
+    /// it can no longer be inferred from the borrowed query type `Q` alone.
+    pub fn remove<K, Q>(&mut self, key: &Q) -> Option<T>
+    where
+        K: 'static + Eq + Hash + Borrow<Q>,
+        Q: 'static + Eq + Hash + ?Sized,
+    {
+        self.map_mut::<K>().remove(key).inspect(|_| {
             self.length -= 1;
-            value
         })
     }
 
     /// Gets an immutable reference to the value corresponding to the given key.
-    pub fn get<K: 'static + Eq + Hash>(&self, key: &K) -> Option<&T> {
+    ///
+    /// The key type `K` stored in the map must be specified explicitly (e.g. via turbofish), since
+    /// it can no longer be inferred from the borrowed query type `Q` alone.
+    pub fn get<K, Q>(&self, key: &Q) -> Option<&T>
+    where
+        K: 'static + Eq + Hash + Borrow<Q>,
+        Q: 'static + Eq + Hash + ?Sized,
+    {
         self.map::<K>().and_then(|map| map.get(key))
     }
 
     /// Gets a mutable reference to the value corresponding to the given key.
-    pub fn get_mut<K: 'static + Eq + Hash>(&mut self, key: &K) -> Option<&mut T> {
+    ///
+    /// The key type `K` stored in the map must be specified explicitly (e.g. via turbofish), since
+    /// it can no longer be inferred from the borrowed query type `Q` alone.
+    pub fn get_mut<K, Q>(&mut self, key: &Q) -> Option<&mut T>
+    where
+        K: 'static + Eq + Hash + Borrow<Q>,
+        Q: 'static + Eq + Hash + ?Sized,
+    {
         self.map_mut::<K>().get_mut(key)
     }
 
+    /// Returns `true` if the map contains a value of type `K` for the given key.
+    ///
+    /// The key type `K` stored in the map must be specified explicitly (e.g. via turbofish), since
+    /// it can no longer be inferred from the borrowed query type `Q` alone.
+    pub fn contains_key<K, Q>(&self, key: &Q) -> bool
+    where
+        K: 'static + Eq + Hash + Borrow<Q>,
+        Q: 'static + Eq + Hash + ?Sized,
+    {
+        self.map::<K>().is_some_and(|map| map.contains_key(key))
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    pub fn entry<K: 'static + Eq + Hash>(&mut self, key: K) -> Entry<'_, K, T> {
+        let map: &mut HashMap<K, T> = self
+            .maps
+            .entry(TypeId::of::<K>())
+            .or_insert_with(ErasedMap::new::<K>)
+            .map
+            .downcast_mut()
+            .expect("two different types should not have the same TypeId");
+
+        match map.entry(key) {
+            hash_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry {
+                entry,
+                length: &mut self.length,
+            }),
+            hash_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                entry,
+                length: &mut self.length,
+            }),
+        }
+    }
+
+    /// Returns an iterator over all the values in the map, across every key type.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.maps
+            .values()
+            .flat_map(|erased| (erased.values)(erased.map.as_ref()))
+    }
+
+    /// Returns an iterator that allows modifying all the values in the map, across every key type.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.maps
+            .values_mut()
+            .flat_map(|erased| (erased.values_mut)(erased.map.as_mut()))
+    }
+
+    /// Returns an iterator over the key-value pairs for a single key type `K`.
+    pub fn iter_keys<K: 'static>(&self) -> impl Iterator<Item = (&K, &T)> {
+        self.map::<K>().into_iter().flat_map(HashMap::iter)
+    }
+
+    /// Reserves capacity for at least `additional` more key types in the outer, `TypeId`-keyed map.
+    pub fn reserve(&mut self, additional: usize) {
+        self.maps.reserve(additional);
+    }
+
+    /// Clears the map, removing every key type and every value, and resetting [`MultiTypeMap::len`] to `0`.
+    pub fn clear(&mut self) {
+        self.maps.clear();
+        self.length = 0;
+    }
+
+    /// Retains only the key-value pairs of a single key type `K` for which `f` returns `true`,
+    /// removing the rest and adjusting [`MultiTypeMap::len`] accordingly. Key types other than `K`
+    /// are left untouched.
+    pub fn retain<K: 'static, F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &T) -> bool,
+    {
+        if let Some(erased) = self.maps.get_mut(&TypeId::of::<K>()) {
+            let map = erased
+                .map
+                .downcast_mut::<HashMap<K, T>>()
+                .expect("two different types should not have the same TypeId");
+            let length = &mut self.length;
+            map.retain(|key, value| {
+                let keep = f(key, value);
+                if !keep {
+                    *length -= 1;
+                }
+                keep
+            });
+        }
+    }
+
+    /// Removes and returns an iterator over all the key-value pairs of a single key type `K`,
+    /// adjusting [`MultiTypeMap::len`] accordingly.
+    pub fn drain_type<K: 'static>(&mut self) -> DrainType<'_, K, T> {
+        match self.maps.get_mut(&TypeId::of::<K>()) {
+            Some(erased) => {
+                let map = erased
+                    .map
+                    .downcast_mut::<HashMap<K, T>>()
+                    .expect("two different types should not have the same TypeId");
+                self.length -= map.len();
+                DrainType::Some(map.drain())
+            }
+            None => DrainType::None,
+        }
+    }
+
     /// Gets an immutable reference to the map for the given key type.
     fn map<K: 'static>(&self) -> Option<&HashMap<K, T>> {
-        self.maps.get(&TypeId::of::<K>()).map(|map| {
-            map.downcast_ref::<HashMap<K, T>>()
+        self.maps.get(&TypeId::of::<K>()).map(|erased| {
+            erased
+                .map
+                .downcast_ref::<HashMap<K, T>>()
                 .expect("two different types should not have the same TypeId")
         })
     }
@@ -64,7 +248,8 @@ impl<T: 'static> MultiTypeMap<T> {
     fn map_mut<K: 'static>(&mut self) -> &mut HashMap<K, T> {
         self.maps
             .entry(TypeId::of::<K>())
-            .or_insert_with(|| Box::<HashMap<K, T>>::default())
+            .or_insert_with(ErasedMap::new::<K>)
+            .map
             .downcast_mut()
             .expect("two different types should not have the same TypeId")
     }
@@ -78,9 +263,159 @@ impl<T: 'static> MultiTypeMap<T> {
     }
 }
 
-impl<T: 'static> Default for MultiTypeMap<T> {
+impl<T: 'static, S: Default> Default for MultiTypeMap<T, S> {
     fn default() -> Self {
-        Self::new()
+        Self {
+            maps: HashMap::default(),
+            length: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A [`Hasher`] tailored to [`TypeId`] keys.
+///
+/// A [`TypeId`] already hashes itself as a single 64-bit value (via `write_u64`, or `write` with
+/// exactly 8 bytes), so there's no real hashing to do: this just captures that value and returns
+/// it unchanged, turning outer-map lookups into a near-direct probe. Mirrors the approach used
+/// internally by the `anymap` crate.
+#[derive(Default)]
+pub struct TypeIdHasher(u64);
+
+impl Hasher for TypeIdHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        debug_assert_eq!(
+            bytes.len(),
+            8,
+            "a TypeId should only ever write a single u64"
+        );
+        self.0 = u64::from_ne_bytes(bytes.try_into().unwrap());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A view into a single entry in a [`MultiTypeMap`], which may either be vacant or occupied.
+///
+/// This enum mirrors [`std::collections::hash_map::Entry`] and is constructed by
+/// [`MultiTypeMap::entry`].
+pub enum Entry<'a, K, T> {
+    Occupied(OccupiedEntry<'a, K, T>),
+    Vacant(VacantEntry<'a, K, T>),
+}
+
+impl<'a, K, T> Entry<'a, K, T> {
+    /// Ensures a value is in the entry by inserting the given value if it was vacant, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the given function if it was
+    /// vacant, and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts.
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K, T: Default> Entry<'a, K, T> {
+    /// Ensures a value is in the entry by inserting [`Default::default`] if it was vacant, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(T::default()),
+        }
+    }
+}
+
+/// A view into an occupied entry in a [`MultiTypeMap`]. Part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, K, T> {
+    entry: hash_map::OccupiedEntry<'a, K, T>,
+    length: &'a mut usize,
+}
+
+impl<'a, K, T> OccupiedEntry<'a, K, T> {
+    /// Gets an immutable reference to the value in the entry.
+    pub fn get(&self) -> &T {
+        self.entry.get()
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.entry.get_mut()
+    }
+
+    /// Converts the entry into a mutable reference to the value in the map, with a lifetime
+    /// bound to the map itself.
+    pub fn into_mut(self) -> &'a mut T {
+        self.entry.into_mut()
+    }
+
+    /// Sets the value of the entry, and returns the old value.
+    pub fn insert(&mut self, value: T) -> T {
+        self.entry.insert(value)
+    }
+
+    /// Takes the value out of the entry, and removes it from the map.
+    pub fn remove(self) -> T {
+        *self.length -= 1;
+        self.entry.remove()
+    }
+}
+
+/// A view into a vacant entry in a [`MultiTypeMap`]. Part of the [`Entry`] enum.
+pub struct VacantEntry<'a, K, T> {
+    entry: hash_map::VacantEntry<'a, K, T>,
+    length: &'a mut usize,
+}
+
+impl<'a, K, T> VacantEntry<'a, K, T> {
+    /// Sets the value of the entry, and returns a mutable reference to it.
+    pub fn insert(self, value: T) -> &'a mut T {
+        *self.length += 1;
+        self.entry.insert(value)
+    }
+}
+
+/// An iterator that drains all the key-value pairs of a single key type `K` from a
+/// [`MultiTypeMap`]. Returned by [`MultiTypeMap::drain_type`].
+pub enum DrainType<'a, K, T> {
+    Some(hash_map::Drain<'a, K, T>),
+    None,
+}
+
+impl<'a, K, T> Iterator for DrainType<'a, K, T> {
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DrainType::Some(drain) => drain.next(),
+            DrainType::None => None,
+        }
     }
 }
 
@@ -96,9 +431,9 @@ mod tests {
         map.insert(1, 1);
         map.insert("hey", 3);
 
-        assert_eq!(map.remove(&false), Some(2));
-        assert_eq!(map.remove(&1), Some(1));
-        assert_eq!(map.remove(&"hey"), Some(3));
+        assert_eq!(map.remove::<bool, _>(&false), Some(2));
+        assert_eq!(map.remove::<i32, _>(&1), Some(1));
+        assert_eq!(map.remove::<&str, _>(&"hey"), Some(3));
     }
 
     #[test]
@@ -108,13 +443,17 @@ mod tests {
         map.insert(false, 0);
         map.insert("hey", 3);
 
-        assert_eq!(map.get_mut(&false), Some(&mut 0));
-        map.get_mut(&false).map(|v| *v = 1);
-        assert_eq!(map.get_mut(&false), Some(&mut 1));
+        assert_eq!(map.get_mut::<bool, _>(&false), Some(&mut 0));
+        if let Some(v) = map.get_mut::<bool, _>(&false) {
+            *v = 1;
+        }
+        assert_eq!(map.get_mut::<bool, _>(&false), Some(&mut 1));
 
-        assert_eq!(map.get_mut(&"hey"), Some(&mut 3));
-        map.get_mut(&"hey").map(|v| *v = 4);
-        assert_eq!(map.get_mut(&"hey"), Some(&mut 4));
+        assert_eq!(map.get_mut::<&str, _>(&"hey"), Some(&mut 3));
+        if let Some(v) = map.get_mut::<&str, _>(&"hey") {
+            *v = 4;
+        }
+        assert_eq!(map.get_mut::<&str, _>(&"hey"), Some(&mut 4));
     }
 
     #[test]
@@ -130,7 +469,154 @@ mod tests {
         assert_eq!(map.insert("foo".to_owned(), 4), Some(3));
 
         // We can still get the `&str` key.
-        assert_eq!(map.get(&"foo"), Some(&2));
-        assert_eq!(map.get(&"foo".to_owned()), Some(&4));
+        assert_eq!(map.get::<&str, _>(&"foo"), Some(&2));
+        assert_eq!(map.get::<String, _>(&"foo".to_owned()), Some(&4));
+
+        // And, thanks to `Borrow`, the `String` key can also be queried with a plain `&str`,
+        // without allocating an owned `String` just to look it up.
+        assert_eq!(map.get::<String, _>("foo"), Some(&4));
+        assert!(map.contains_key::<String, _>("foo"));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = MultiTypeMap::new();
+
+        assert_eq!(map.entry(false).or_insert(1), &mut 1);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.entry(false).or_insert(2), &mut 1);
+        assert_eq!(map.len(), 1);
+
+        assert_eq!(map.entry("hey").or_insert_with(|| 3), &mut 3);
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.entry(1).or_default(), &mut 0);
+        assert_eq!(map.len(), 3);
+
+        map.entry(false).and_modify(|v| *v += 1);
+        assert_eq!(map.get::<bool, _>(&false), Some(&2));
+        assert_eq!(map.len(), 3);
+
+        match map.entry(false) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 2),
+            Entry::Vacant(_) => panic!("expected an occupied entry"),
+        }
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get::<bool, _>(&false), None);
+    }
+
+    #[test]
+    fn test_type_id_hasher() {
+        let mut hasher = TypeIdHasher::default();
+        hasher.write_u64(0x1234_5678_9abc_def0);
+        assert_eq!(hasher.finish(), 0x1234_5678_9abc_def0);
+
+        // The default `MultiTypeMap` hasher is backed by `TypeIdHasher`, and it should behave
+        // exactly like the regular map for any number of key types.
+        let mut map = MultiTypeMap::new();
+        map.insert(false, 1);
+        map.insert(1, 2);
+        map.insert("hey", 3);
+
+        assert_eq!(map.get::<bool, _>(&false), Some(&1));
+        assert_eq!(map.get::<i32, _>(&1), Some(&2));
+        assert_eq!(map.get::<&str, _>(&"hey"), Some(&3));
+    }
+
+    #[test]
+    fn test_values() {
+        let mut map = MultiTypeMap::new();
+
+        map.insert(false, 1);
+        map.insert(1, 2);
+        map.insert("hey", 3);
+
+        let mut values: Vec<_> = map.values().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        for value in map.values_mut() {
+            *value *= 10;
+        }
+
+        let mut values: Vec<_> = map.values().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_iter_keys() {
+        let mut map = MultiTypeMap::new();
+
+        map.insert(1, "one");
+        map.insert(2, "two");
+        map.insert("hey", "there");
+
+        let mut pairs: Vec<_> = map.iter_keys::<i32>().collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec![(&1, &"one"), (&2, &"two")]);
+
+        assert_eq!(map.iter_keys::<bool>().next(), None);
+    }
+
+    #[test]
+    fn test_with_capacity_and_reserve() {
+        let mut map = MultiTypeMap::<i32>::with_capacity(4);
+        assert!(map.is_empty());
+
+        map.reserve(8);
+        map.insert(false, 1);
+        assert_eq!(map.get::<bool, _>(&false), Some(&1));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut map = MultiTypeMap::new();
+
+        map.insert(false, 1);
+        map.insert("hey", 2);
+        assert_eq!(map.len(), 2);
+
+        map.clear();
+        assert_eq!(map.len(), 0);
+        assert!(map.is_empty());
+        assert_eq!(map.get::<bool, _>(&false), None);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut map = MultiTypeMap::new();
+
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.insert(3, 30);
+        map.insert("hey", 40);
+
+        map.retain::<i32, _>(|_, value| *value >= 20);
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get::<i32, _>(&1), None);
+        assert_eq!(map.get::<i32, _>(&2), Some(&20));
+        assert_eq!(map.get::<i32, _>(&3), Some(&30));
+        assert_eq!(map.get::<&str, _>(&"hey"), Some(&40));
+    }
+
+    #[test]
+    fn test_drain_type() {
+        let mut map = MultiTypeMap::new();
+
+        map.insert(1, "one");
+        map.insert(2, "two");
+        map.insert("hey", "there");
+
+        let mut drained: Vec<_> = map.drain_type::<i32>().collect();
+        drained.sort_unstable();
+        assert_eq!(drained, vec![(1, "one"), (2, "two")]);
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get::<i32, _>(&1), None);
+        assert_eq!(map.get::<&str, _>(&"hey"), Some(&"there"));
+
+        assert_eq!(map.drain_type::<bool>().next(), None);
     }
 }
@@ -0,0 +1,171 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::MultiTypeMap;
+
+/// The empty type-state list, used as the default `TS` for [`TypedMultiTypeMap`].
+pub struct Nil;
+
+/// A type-state cons cell recording that `K` has been inserted into a [`TypedMultiTypeMap`],
+/// alongside the other key types already recorded in `Rest`.
+pub struct Cons<K, Rest>(PhantomData<(K, Rest)>);
+
+/// Witnesses that the key type is at the head of the type-state list. Used as the `Index`
+/// parameter of [`Contains`].
+pub struct Here;
+
+/// Witnesses that the key type is reached by skipping past the head and recursing into `Rest`
+/// with the index `I`. Used as the `Index` parameter of [`Contains`].
+pub struct There<I>(PhantomData<I>);
+
+/// Witnesses that a key type `K` is present somewhere in the type-state list `Self`, having been
+/// found at the position described by `Index`. Implemented recursively over [`Cons`] so that
+/// [`TypedMultiTypeMap::get_unchecked`] only compiles when the type system can prove `K` was
+/// inserted.
+pub trait Contains<K, Index> {}
+
+impl<K, Rest> Contains<K, Here> for Cons<K, Rest> {}
+
+impl<K, Other, Rest, Index> Contains<K, There<Index>> for Cons<Other, Rest> where
+    Rest: Contains<K, Index>
+{
+}
+
+/// A [`MultiTypeMap`] variant that tracks, at the type level, which key *types* have been
+/// inserted so far via the type-state list `TS`.
+///
+/// The type-state only proves that *some* key of a given type was inserted at some point, not
+/// that any particular key is present: like a plain [`MultiTypeMap`], a single key type can hold
+/// any number of distinct keys (insert `1i32` and `2i32` and both live under the same `i32` key
+/// type). So [`TypedMultiTypeMap::get_unchecked`] still panics if `key` itself was never inserted,
+/// even though `TS` proves its type was used — the type-state only rules out "no key of this type
+/// exists at all", not "this exact key is missing". Callers that use a single, well-known key per
+/// type (e.g. a handful of marker/config types used as singleton slots) get a genuine
+/// compile-time guarantee from this; callers who insert several distinct keys per type do not,
+/// and should reach for a plain [`MultiTypeMap::get`] instead.
+///
+/// Aside from that caveat, it's a zero-cost `PhantomData`-carrying newtype over [`MultiTypeMap`]:
+/// the runtime representation is unchanged, and [`TypedMultiTypeMap::into_inner`] recovers the
+/// plain map at no cost.
+pub struct TypedMultiTypeMap<T, TS = Nil> {
+    map: MultiTypeMap<T>,
+    _state: PhantomData<TS>,
+}
+
+impl<T: 'static> TypedMultiTypeMap<T, Nil> {
+    /// Creates an empty [`TypedMultiTypeMap`] whose type-state records no key types yet.
+    pub fn new() -> Self {
+        Self {
+            map: MultiTypeMap::new(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static> Default for TypedMultiTypeMap<T, Nil> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: 'static, TS> TypedMultiTypeMap<T, TS> {
+    /// Inserts a value for key type `K`, returning a map whose type-state additionally records
+    /// that `K` is now present.
+    pub fn insert<K: 'static + Eq + Hash>(
+        mut self,
+        key: K,
+        value: T,
+    ) -> TypedMultiTypeMap<T, Cons<K, TS>> {
+        self.map.insert(key, value);
+        TypedMultiTypeMap {
+            map: self.map,
+            _state: PhantomData,
+        }
+    }
+
+    /// Gets an immutable reference to the value for `key`, without an `Option`: the type-state
+    /// `TS` proves that *a* key of type `K` was inserted.
+    ///
+    /// This still panics if `key` itself is not one of the `K` keys actually inserted — see the
+    /// type-level docs above for why `TS` can't rule that out.
+    pub fn get_unchecked<K: 'static + Eq + Hash, Index>(&self, key: &K) -> &T
+    where
+        TS: Contains<K, Index>,
+    {
+        self.map
+            .get::<K, _>(key)
+            .expect("no value present for this key, even though its key type was inserted")
+    }
+
+    /// Gets a mutable reference to the value for `key`, without an `Option`: the type-state `TS`
+    /// proves that *a* key of type `K` was inserted.
+    ///
+    /// This still panics if `key` itself is not one of the `K` keys actually inserted — see the
+    /// type-level docs above for why `TS` can't rule that out.
+    pub fn get_unchecked_mut<K: 'static + Eq + Hash, Index>(&mut self, key: &K) -> &mut T
+    where
+        TS: Contains<K, Index>,
+    {
+        self.map
+            .get_mut::<K, _>(key)
+            .expect("no value present for this key, even though its key type was inserted")
+    }
+
+    /// Drops the type-state, returning the underlying plain [`MultiTypeMap`].
+    pub fn into_inner(self) -> MultiTypeMap<T> {
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_insert_and_get_unchecked() {
+        let map = TypedMultiTypeMap::new()
+            .insert(false, 1)
+            .insert("hey", 2)
+            .insert(3i32, 3);
+
+        assert_eq!(map.get_unchecked(&false), &1);
+        assert_eq!(map.get_unchecked(&"hey"), &2);
+        assert_eq!(map.get_unchecked(&3i32), &3);
+    }
+
+    #[test]
+    fn test_typed_get_unchecked_mut() {
+        let mut map = TypedMultiTypeMap::new().insert(false, 1);
+
+        *map.get_unchecked_mut(&false) += 1;
+        assert_eq!(map.get_unchecked(&false), &2);
+    }
+
+    #[test]
+    fn test_typed_into_inner() {
+        let map = TypedMultiTypeMap::new().insert(false, 1).into_inner();
+        assert_eq!(map.get::<bool, _>(&false), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "no value present for this key")]
+    fn test_typed_get_unchecked_panics_for_unknown_key_of_known_type() {
+        // `TS` only proves that *some* `i32` key was inserted, not that `3` specifically was.
+        let map = TypedMultiTypeMap::new()
+            .insert(1i32, "one")
+            .insert(2i32, "two");
+        map.get_unchecked::<i32, Here>(&3i32);
+    }
+
+    #[test]
+    fn test_typed_get_unchecked_with_explicit_index() {
+        // With two `i32` keys in the type-state, `Index` is ambiguous and must be spelled out
+        // explicitly; `Here`/`There` are re-exported from the crate root so callers can do so.
+        let map = TypedMultiTypeMap::new()
+            .insert(1i32, "one")
+            .insert(2i32, "two");
+
+        assert_eq!(map.get_unchecked::<i32, Here>(&2), &"two");
+        assert_eq!(map.get_unchecked::<i32, There<Here>>(&1), &"one");
+    }
+}